@@ -0,0 +1,19 @@
+//! This module contains the implementation of a virtual DOM tree: types
+//! that describe what should be on screen (`VNode` and friends), diffed
+//! against what's there now to patch only what changed.
+
+mod vdiff;
+mod vnode;
+mod vtag;
+mod vtext;
+mod vcomp;
+mod vlist;
+mod patch;
+
+pub use self::vdiff::VDiff;
+pub use self::vnode::VNode;
+pub use self::vtag::VTag;
+pub use self::vtext::VText;
+pub use self::vcomp::VComp;
+pub use self::vlist::VList;
+pub use self::patch::{apply_patches, diff, Patch};
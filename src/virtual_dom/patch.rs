@@ -0,0 +1,450 @@
+//! This module contains the `Patch` type, a serializable description of
+//! the DOM mutations needed to turn one `VNode` tree into another.
+
+use stdweb::unstable::TryInto;
+use stdweb::web::{document, Element, IElement, INode, IParentNode, Node};
+use stdweb::js;
+use html::Component;
+use super::VNode;
+
+/// A single DOM mutation, anchored to a node reached by a depth-first
+/// walk of the *rendered DOM*, not the virtual tree (root = 0, first
+/// child = 1, first grandchild = 2, ...). `VList` fragments don't
+/// themselves occupy a slot in that walk, since they have no DOM node of
+/// their own — only the real elements/text nodes they (transitively)
+/// render do. `apply_patches` resolves indices the same way, by walking
+/// `root`'s actual children, so the two stay in lockstep.
+///
+/// Produced by `diff` and consumed by `apply_patches`, so "what changed"
+/// can be computed, sent across a channel, and replayed somewhere a
+/// `VNode` tree isn't available.
+#[derive(Debug, PartialEq)]
+pub enum Patch {
+    /// Append freshly rendered HTML under the node at `index` (or, if
+    /// `None`, directly under the patched root).
+    AppendChildren(Option<usize>, String),
+    /// Replace the node at `index` with freshly rendered HTML.
+    Replace(usize, String),
+    /// Drop the last `count` children under the node at `index` (or, if
+    /// `None`, directly under the patched root).
+    TruncateChildren(Option<usize>, usize),
+    /// Add or overwrite attributes on the node at `index`.
+    AddAttributes(usize, Vec<(String, String)>),
+    /// Remove attributes by name from the node at `index`.
+    RemoveAttributes(usize, Vec<String>),
+    /// Replace the text content of the node at `index`.
+    ChangeText(usize, String),
+}
+
+/// Walks `old` and `new` in lockstep, assigning each *rendered* node the
+/// same depth-first index `apply_patches` resolves it by, and collects
+/// the patches needed to turn `old` into `new`.
+pub fn diff<CTX, COMP: Component<CTX>>(
+    old: &VNode<CTX, COMP>,
+    new: &VNode<CTX, COMP>,
+) -> Vec<Patch>
+where
+    COMP::Properties: PartialEq,
+{
+    let mut patches = Vec::new();
+    let mut index = 0;
+    diff_node(old, new, &mut index, &mut patches);
+    patches
+}
+
+fn diff_node<CTX, COMP: Component<CTX>>(
+    old: &VNode<CTX, COMP>,
+    new: &VNode<CTX, COMP>,
+    index: &mut usize,
+    patches: &mut Vec<Patch>,
+)
+where
+    COMP::Properties: PartialEq,
+{
+    match (old, new) {
+        (&VNode::VList(ref old_list), &VNode::VList(ref new_list)) => {
+            // A `VList` has no DOM node of its own, so it doesn't
+            // consume an index; its children slot directly into the
+            // surrounding walk, anchored on the enclosing DOM parent.
+            diff_children(&old_list.childs, &new_list.childs, index, patches, None);
+        }
+        (&VNode::VComp(ref old_comp), &VNode::VComp(ref new_comp))
+            if old_comp.type_id == new_comp.type_id && old_comp.props == new_comp.props =>
+        {
+            // Unchanged: no patch, but still advance past every DOM node
+            // this subtree rendered so later siblings resolve correctly.
+            *index += dom_node_count(new);
+        }
+        (&VNode::VTag(ref old_tag), &VNode::VTag(ref new_tag)) if old_tag.tag == new_tag.tag => {
+            let this_index = *index;
+            *index += 1;
+
+            let mut added = Vec::new();
+            for (name, value) in new_tag.attributes.iter() {
+                if old_tag.attributes.get(name) != Some(value) {
+                    added.push((name.clone(), value.clone()));
+                }
+            }
+            if !added.is_empty() {
+                patches.push(Patch::AddAttributes(this_index, added));
+            }
+
+            let removed: Vec<String> = old_tag.attributes.keys()
+                .filter(|name| !new_tag.attributes.contains_key(*name))
+                .cloned()
+                .collect();
+            if !removed.is_empty() {
+                patches.push(Patch::RemoveAttributes(this_index, removed));
+            }
+
+            diff_children(&old_tag.childs, &new_tag.childs, index, patches, Some(this_index));
+        }
+        (&VNode::VText(ref old_text), &VNode::VText(ref new_text)) => {
+            let this_index = *index;
+            *index += 1;
+            if old_text.text != new_text.text {
+                patches.push(Patch::ChangeText(this_index, new_text.text.clone()));
+            }
+        }
+        (&VNode::VRef(ref old_node), &VNode::VRef(ref new_node)) if old_node == new_node => {
+            *index += 1 + count_descendants(old_node);
+        }
+        _ => {
+            // Either a different kind of node, a `VTag` with a different
+            // tag name, or a changed `VComp`/`VRef` we can't patch
+            // in place: replace the whole subtree. Later siblings still
+            // resolve against the *old*, not-yet-mutated DOM, so advance
+            // past however many nodes `old` (not `new`) occupies there.
+            let this_index = *index;
+            patches.push(Patch::Replace(this_index, new.to_string()));
+            *index += dom_node_count(old);
+        }
+    }
+}
+
+/// Diffs a list of children that share `parent_index` as their common
+/// DOM parent (`None` meaning the root passed to `apply_patches`).
+fn diff_children<CTX, COMP: Component<CTX>>(
+    old_childs: &[VNode<CTX, COMP>],
+    new_childs: &[VNode<CTX, COMP>],
+    index: &mut usize,
+    patches: &mut Vec<Patch>,
+    parent_index: Option<usize>,
+)
+where
+    COMP::Properties: PartialEq,
+{
+    let mut old_iter = old_childs.iter();
+    let mut new_iter = new_childs.iter();
+    loop {
+        match (old_iter.next(), new_iter.next()) {
+            (Some(o), Some(n)) => diff_node(o, n, index, patches),
+            (Some(first_dropped), None) => {
+                // `apply_resolved` drops DOM children, not `VNode`s, so
+                // a dropped child that's a `VList` (or a `VComp`
+                // rendering one) must count every DOM node it places
+                // directly under the parent, not just itself.
+                let dropped: Vec<_> = Some(first_dropped).into_iter().chain(old_iter).collect();
+                let removed = dropped.iter().map(|n| top_level_count(*n)).sum();
+                patches.push(Patch::TruncateChildren(parent_index, removed));
+                // The dropped nodes (and their descendants) still occupy
+                // slots in the *old* DOM `index` is counted against.
+                *index += dropped.iter().map(|n| dom_node_count(*n)).sum::<usize>();
+                break;
+            }
+            (None, Some(first_extra)) => {
+                // These children have no old counterpart, so they don't
+                // occupy a slot in the *old* DOM `index` is counted
+                // against; appending them must not advance `index`.
+                let mut html = first_extra.to_string();
+                for n in new_iter {
+                    html.push_str(&n.to_string());
+                }
+                patches.push(Patch::AppendChildren(parent_index, html));
+                break;
+            }
+            (None, None) => break,
+        }
+    }
+}
+
+/// Counts how many real DOM nodes `node` renders to (itself plus every
+/// descendant), the same unit `diff`'s indices are counted in.
+fn dom_node_count<CTX, COMP: Component<CTX>>(node: &VNode<CTX, COMP>) -> usize {
+    match *node {
+        VNode::VTag(ref vtag) => 1 + vtag.childs.iter().map(dom_node_count).sum::<usize>(),
+        VNode::VText(_) => 1,
+        VNode::VRef(ref node) => 1 + count_descendants(node),
+        VNode::VList(ref vlist) => vlist.childs.iter().map(dom_node_count).sum(),
+        VNode::VComp(ref vcomp) => dom_node_count(&vcomp.view()),
+    }
+}
+
+/// Counts how many DOM nodes `node` places directly under its parent:
+/// one for a tag, text or raw node, and the sum over its children for a
+/// `VList`, which has no node of its own.
+fn top_level_count<CTX, COMP: Component<CTX>>(node: &VNode<CTX, COMP>) -> usize {
+    match *node {
+        VNode::VTag(_) | VNode::VText(_) | VNode::VRef(_) => 1,
+        VNode::VList(ref vlist) => vlist.childs.iter().map(top_level_count).sum(),
+        VNode::VComp(ref vcomp) => top_level_count(&vcomp.view()),
+    }
+}
+
+fn count_descendants(node: &Node) -> usize {
+    node.child_nodes().iter().map(|child| 1 + count_descendants(&child)).sum()
+}
+
+/// Resolves each patch's target(s) against `root` up front (before any
+/// mutation), then applies them. Resolving everything before mutating
+/// anything means an earlier patch's mutation can't shift the indices a
+/// later patch in the same batch was computed against.
+pub fn apply_patches(root: &Element, patches: &[Patch]) {
+    let root_node: Node = root.to_owned().into();
+
+    let resolved: Vec<Option<ResolvedOp>> = patches.iter()
+        .map(|patch| resolve_patch(&root_node, patch))
+        .collect();
+
+    for op in resolved {
+        if let Some(op) = op {
+            apply_resolved(op);
+        }
+    }
+}
+
+enum ResolvedOp {
+    Replace(Node, String),
+    AppendChildren(Node, String),
+    TruncateChildren(Node, usize),
+    AddAttributes(Node, Vec<(String, String)>),
+    RemoveAttributes(Node, Vec<String>),
+    ChangeText(Node, String),
+}
+
+fn resolve_patch(root_node: &Node, patch: &Patch) -> Option<ResolvedOp> {
+    match *patch {
+        Patch::Replace(index, ref html) => {
+            resolve_in_children(root_node, index).map(|node| ResolvedOp::Replace(node, html.clone()))
+        }
+        Patch::AppendChildren(parent_index, ref html) => {
+            resolve_parent(root_node, parent_index)
+                .map(|node| ResolvedOp::AppendChildren(node, html.clone()))
+        }
+        Patch::TruncateChildren(parent_index, count) => {
+            resolve_parent(root_node, parent_index)
+                .map(|node| ResolvedOp::TruncateChildren(node, count))
+        }
+        Patch::AddAttributes(index, ref attrs) => {
+            resolve_in_children(root_node, index).map(|node| ResolvedOp::AddAttributes(node, attrs.clone()))
+        }
+        Patch::RemoveAttributes(index, ref names) => {
+            resolve_in_children(root_node, index).map(|node| ResolvedOp::RemoveAttributes(node, names.clone()))
+        }
+        Patch::ChangeText(index, ref text) => {
+            resolve_in_children(root_node, index).map(|node| ResolvedOp::ChangeText(node, text.clone()))
+        }
+    }
+}
+
+fn resolve_parent(root_node: &Node, parent_index: Option<usize>) -> Option<Node> {
+    match parent_index {
+        Some(index) => resolve_in_children(root_node, index),
+        None => Some(root_node.to_owned()),
+    }
+}
+
+fn apply_resolved(op: ResolvedOp) {
+    match op {
+        ResolvedOp::Replace(node, html) => {
+            if let Some(parent) = node.parent_node() {
+                let parent: Element = parent.try_into().expect("patch parent isn't an element");
+                let fresh = parse_html(&html);
+                parent.insert_before(&fresh, &node).expect("can't insert replacement node");
+                parent.remove_child(&node).expect("can't remove replaced node");
+            }
+        }
+        ResolvedOp::AppendChildren(parent, html) => {
+            let parent: Element = parent.try_into().expect("append target isn't an element");
+            parent.append_child(&parse_html(&html));
+        }
+        ResolvedOp::TruncateChildren(parent, count) => {
+            for _ in 0..count {
+                if let Some(last) = parent.last_child() {
+                    parent.remove_child(&last).expect("can't truncate child");
+                }
+            }
+        }
+        ResolvedOp::AddAttributes(node, attrs) => {
+            let element: Element = node.try_into().expect("attribute patch target isn't an element");
+            for (name, value) in attrs {
+                element.set_attribute(&name, &value).expect("can't set attribute");
+            }
+        }
+        ResolvedOp::RemoveAttributes(node, names) => {
+            let element: Element = node.try_into().expect("attribute patch target isn't an element");
+            for name in names {
+                element.remove_attribute(&name);
+            }
+        }
+        ResolvedOp::ChangeText(node, text) => {
+            node.set_text_content(&text);
+        }
+    }
+}
+
+/// Parses an HTML snippet into a document fragment via a detached
+/// `<template>` element, so `Replace`/`AppendChildren` can insert freshly
+/// rendered markup without a `VNode` tree to walk. Inserting the
+/// fragment inserts every top-level node the snippet contains.
+fn parse_html(html: &str) -> Node {
+    let template = document().create_element("template").expect("can't create template");
+    template.set_property("innerHTML", html).expect("can't set innerHTML");
+    (js! {
+        return @{&template}.content;
+    }).try_into().expect("patch fragment didn't parse to a node")
+}
+
+fn resolve_in_children(parent: &Node, target: usize) -> Option<Node> {
+    let mut counter = 0;
+    for child in parent.child_nodes() {
+        if let Some(found) = resolve_walk(&child, target, &mut counter) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn resolve_walk(node: &Node, target: usize, counter: &mut usize) -> Option<Node> {
+    let this_index = *counter;
+    *counter += 1;
+    if this_index == target {
+        return Some(node.to_owned());
+    }
+    for child in node.child_nodes() {
+        if let Some(found) = resolve_walk(&child, target, counter) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{VList, VTag, VText};
+    use html::ScopeEnv;
+    use stdweb::web::document;
+
+    struct Ctx;
+
+    struct Comp;
+
+    impl Component<Ctx> for Comp {
+        type Message = ();
+        type Properties = ();
+
+        fn create(_: Self::Properties, _: ScopeEnv<Ctx, Self>) -> Self {
+            Comp
+        }
+
+        fn update(&mut self, _: Self::Message) -> bool {
+            false
+        }
+    }
+
+    fn list(childs: Vec<VNode<Ctx, Comp>>) -> VNode<Ctx, Comp> {
+        VList::from(childs).into()
+    }
+
+    fn tag(name: &str, childs: Vec<VNode<Ctx, Comp>>) -> VNode<Ctx, Comp> {
+        let mut vtag: VTag<Ctx, Comp> = VTag::new(name);
+        for child in childs {
+            vtag.add_child(child);
+        }
+        vtag.into()
+    }
+
+    fn text(s: &str) -> VNode<Ctx, Comp> {
+        VText::new(s.to_string()).into()
+    }
+
+    /// Reproduces the case where a matched child grows children of its
+    /// own: `diff`'s indices must keep counting the *old* DOM, so the
+    /// later sibling's patch still lands on the right node.
+    #[test]
+    fn diff_indexes_later_siblings_against_the_old_tree() {
+        let old = list(vec![tag("div", vec![]), text("x")]);
+        let new = list(vec![tag("div", vec![text("a")]), text("y")]);
+
+        let patches = diff(&old, &new);
+
+        assert_eq!(
+            patches,
+            vec![
+                Patch::AppendChildren(Some(0), "a".to_string()),
+                Patch::ChangeText(1, "y".to_string()),
+            ]
+        );
+    }
+
+    /// A dropped child that's a `VList` flattens to more than one DOM
+    /// node, so truncating it must drop that many DOM children, not one
+    /// per dropped `VNode`.
+    #[test]
+    fn truncate_counts_dom_nodes_not_vnodes_when_dropping_a_vlist_child() {
+        let old = list(vec![
+            tag("div", vec![]),
+            list(vec![tag("span", vec![]), tag("span", vec![])]),
+        ]);
+        let new = list(vec![tag("div", vec![])]);
+
+        let patches = diff(&old, &new);
+
+        assert_eq!(patches, vec![Patch::TruncateChildren(None, 2)]);
+    }
+
+    #[test]
+    fn change_text_round_trips() {
+        let root = document().create_element("div").expect("can't create element");
+        let text = document().create_text_node("before");
+        root.append_child(&text);
+
+        let patches = vec![Patch::ChangeText(0, "after".to_string())];
+        apply_patches(&root, &patches);
+
+        let node: Node = text.into();
+        assert_eq!(node.text_content(), Some("after".to_string()));
+    }
+
+    #[test]
+    fn add_and_remove_attributes_round_trip() {
+        let root = document().create_element("div").expect("can't create element");
+        let child = document().create_element("span").expect("can't create element");
+        child.set_attribute("class", "old").expect("can't set attribute");
+        root.append_child(&child);
+
+        let patches = vec![
+            Patch::AddAttributes(0, vec![("id".to_string(), "new".to_string())]),
+            Patch::RemoveAttributes(0, vec!["class".to_string()]),
+        ];
+        apply_patches(&root, &patches);
+
+        assert_eq!(child.get_attribute("id"), Some("new".to_string()));
+        assert_eq!(child.get_attribute("class"), None);
+    }
+
+    #[test]
+    fn truncate_children_round_trips() {
+        let root = document().create_element("ul").expect("can't create element");
+        for _ in 0..3 {
+            let li = document().create_element("li").expect("can't create element");
+            root.append_child(&li);
+        }
+
+        let patches = vec![Patch::TruncateChildren(None, 2)];
+        apply_patches(&root, &patches);
+
+        assert_eq!(root.child_nodes().len(), 1);
+    }
+}
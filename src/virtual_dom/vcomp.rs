@@ -0,0 +1,83 @@
+//! This module contains the implementation of a component node `VComp`.
+
+use std::any::TypeId;
+use std::rc::Rc;
+use stdweb::web::{Element, Node};
+use html::{Component, ScopeEnv};
+use super::{VDiff, VNode};
+
+/// A type for a virtual component node, bound to its mounted root node
+/// once rendered.
+pub struct VComp<CTX, COMP: Component<CTX>> {
+    /// Identifies which component type this node mounts. Compared
+    /// alongside `props` so two `VComp`s of the *same* component type
+    /// with equal props are recognized as equal, letting diffing
+    /// short-circuit an unchanged subtree (`COMP::Properties` alone
+    /// can't distinguish components that happen to share a props type).
+    pub(crate) type_id: TypeId,
+    /// The props this component was constructed with.
+    pub(crate) props: COMP::Properties,
+    /// An optional key used to match this node across renders.
+    pub key: Option<String>,
+    generator: Rc<Fn() -> VNode<CTX, COMP>>,
+    reference: Option<Node>,
+}
+
+impl<CTX: 'static, COMP: Component<CTX>> VComp<CTX, COMP> {
+    /// Creates a new `VComp` identified by `type_id` and constructed with
+    /// `props`. `generator` recomputes this component's rendered output;
+    /// used for contexts (SSR, patch diffing) that don't have a live
+    /// mounted scope to ask for a view directly.
+    pub fn new<F>(type_id: TypeId, props: COMP::Properties, generator: F) -> Self
+    where
+        F: Fn() -> VNode<CTX, COMP> + 'static,
+    {
+        VComp {
+            type_id,
+            props,
+            key: None,
+            generator: Rc::new(generator),
+            reference: None,
+        }
+    }
+
+    /// Sets the reconciliation key for this node.
+    pub fn set_key<S: Into<String>>(&mut self, key: S) {
+        self.key = Some(key.into());
+    }
+
+    /// Renders this component's current output.
+    pub fn view(&self) -> VNode<CTX, COMP> {
+        (self.generator)()
+    }
+}
+
+impl<CTX: 'static, COMP: Component<CTX>> VDiff for VComp<CTX, COMP> {
+    type Context = CTX;
+    type Component = COMP;
+
+    /// Get binded node.
+    fn get_node(&self) -> Option<Node> {
+        self.reference.clone()
+    }
+
+    /// Remove VComp from parent.
+    fn remove(self, parent: &Element) {
+        if let Some(node) = self.reference {
+            parent.remove_child(&node).expect("can't remove VComp node");
+        }
+    }
+
+    /// Renders this component and lets the rendered tree diff itself
+    /// against the opposite node.
+    fn apply(
+        &mut self,
+        parent: &Element,
+        opposite: Option<VNode<Self::Context, Self::Component>>,
+        env: ScopeEnv<Self::Context, Self::Component>,
+    ) {
+        let mut rendered = self.view();
+        rendered.apply(parent, opposite, env);
+        self.reference = rendered.get_node();
+    }
+}
@@ -0,0 +1,31 @@
+//! This module contains the `VDiff` trait, implemented by everything that
+//! can be diffed against a previous version of itself and patched into a
+//! live DOM subtree.
+
+use stdweb::web::{Element, Node};
+use html::{Component, ScopeEnv};
+use super::VNode;
+
+/// Something that can be rendered into, and diffed against, a live DOM
+/// subtree under `parent`.
+pub trait VDiff {
+    /// The context used by the component tree this node belongs to.
+    type Context;
+    /// The root component of the tree this node belongs to.
+    type Component: Component<Self::Context>;
+
+    /// Returns the bound DOM node, if any.
+    fn get_node(&self) -> Option<Node>;
+
+    /// Removes the bound node (if any) from `parent`.
+    fn remove(self, parent: &Element);
+
+    /// Applies this node to `parent`, diffing against `opposite` (the
+    /// previous version of this node, if any) to patch only what changed.
+    fn apply(
+        &mut self,
+        parent: &Element,
+        opposite: Option<VNode<Self::Context, Self::Component>>,
+        env: ScopeEnv<Self::Context, Self::Component>,
+    );
+}
@@ -0,0 +1,230 @@
+//! This module contains fragment implementation for virtual nodes.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+use stdweb::web::{Element, INode, Node};
+use html::{Component, ScopeEnv};
+use super::{VDiff, VNode};
+
+/// This struct represents a fragment of the Virtual DOM tree. A list of
+/// `VNode`s without a single wrapper element, useful for components
+/// returning multiple siblings from `view()`.
+pub struct VList<CTX, COMP: Component<CTX>> {
+    /// The list of children nodes. Used to control the changes of the
+    /// list in reconciliation algorithm.
+    pub childs: Vec<VNode<CTX, COMP>>,
+}
+
+impl<CTX, COMP: Component<CTX>> VList<CTX, COMP> {
+    /// Creates a new empty `VList` instance.
+    pub fn new() -> Self {
+        VList { childs: Vec::new() }
+    }
+
+    /// Adds new child to this list.
+    pub fn add_child(&mut self, child: VNode<CTX, COMP>) {
+        self.childs.push(child);
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> Default for VList<CTX, COMP> {
+    fn default() -> Self {
+        VList::new()
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> From<Vec<VNode<CTX, COMP>>> for VList<CTX, COMP> {
+    fn from(childs: Vec<VNode<CTX, COMP>>) -> Self {
+        VList { childs }
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> FromIterator<VNode<CTX, COMP>> for VList<CTX, COMP> {
+    fn from_iter<T: IntoIterator<Item = VNode<CTX, COMP>>>(iter: T) -> Self {
+        let mut list = VList::new();
+        for child in iter {
+            list.add_child(child);
+        }
+        list
+    }
+}
+
+impl<CTX: 'static, COMP: Component<CTX>> VDiff for VList<CTX, COMP> {
+    type Context = CTX;
+    type Component = COMP;
+
+    /// Get binded node, uses the first child as the representative node.
+    fn get_node(&self) -> Option<Node> {
+        self.childs.first().and_then(|child| child.get_node())
+    }
+
+    /// Removes all children from parent.
+    fn remove(self, parent: &Element) {
+        for child in self.childs {
+            child.remove(parent);
+        }
+    }
+
+    /// Diffs the list against the opposite list. Keyed children are
+    /// matched up by key rather than position, so reordering a list
+    /// moves existing DOM nodes into place instead of recreating them;
+    /// unkeyed children fall back to positional matching. Old keyed
+    /// children that aren't claimed by the new list are removed.
+    fn apply(
+        &mut self,
+        parent: &Element,
+        opposite: Option<VNode<Self::Context, Self::Component>>,
+        env: ScopeEnv<Self::Context, Self::Component>,
+    ) {
+        let opposite_childs = match opposite {
+            Some(VNode::VList(vlist)) => vlist.childs,
+            Some(other) => vec![other],
+            None => Vec::new(),
+        };
+
+        let mut keyed = HashMap::new();
+        let mut unkeyed = Vec::new();
+        for child in opposite_childs {
+            match child.key().map(str::to_string) {
+                Some(key) => {
+                    keyed.insert(key, child);
+                }
+                None => unkeyed.push(child),
+            }
+        }
+        let mut unkeyed_iter = unkeyed.into_iter();
+
+        let mut previous_node: Option<Node> = None;
+        for child in self.childs.iter_mut() {
+            let opposite_child = match child.key() {
+                Some(key) => keyed.remove(key),
+                None => unkeyed_iter.next(),
+            };
+
+            // A reused keyed node may sit in the wrong spot after a
+            // reorder; move its DOM node into place before patching it.
+            if let Some(ref existing) = opposite_child {
+                if let Some(node) = existing.get_node() {
+                    reposition(parent, &previous_node, &node);
+                }
+            }
+
+            child.apply(parent, opposite_child, env.clone());
+            previous_node = child.get_node();
+        }
+
+        for (_, leftover) in keyed {
+            leftover.remove(parent);
+        }
+        for leftover in unkeyed_iter {
+            leftover.remove(parent);
+        }
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> fmt::Debug for VList<CTX, COMP> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("VList")
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> PartialEq for VList<CTX, COMP>
+where
+    COMP::Properties: PartialEq,
+{
+    fn eq(&self, other: &VList<CTX, COMP>) -> bool {
+        self.childs == other.childs
+    }
+}
+
+/// Moves `node` so it directly follows `previous_node` in `parent` (or
+/// is `parent`'s first child, if `previous_node` is `None`), unless it's
+/// already there. Anchoring on the sibling that follows the
+/// already-placed node, rather than unconditionally appending, keeps
+/// not-yet-processed old children further along in `parent` from being
+/// shoved past the end.
+fn reposition(parent: &Element, previous_node: &Option<Node>, node: &Node) {
+    let anchor = match *previous_node {
+        Some(ref sibling) => sibling.next_sibling(),
+        None => parent.first_child(),
+    };
+    match anchor {
+        Some(ref next) if next != node => {
+            parent.insert_before(node, next)
+                .expect("can't move keyed node into place");
+        }
+        None => {
+            parent.append_child(node);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stdweb::web::{document, IParentNode};
+
+    fn child(id: &str) -> Element {
+        let element = document().create_element("li").expect("can't create element");
+        element.set_attribute("data-id", id).expect("can't set attribute");
+        element
+    }
+
+    /// A node already directly after `previous_node` is left untouched.
+    #[test]
+    fn reposition_leaves_correctly_placed_node_alone() {
+        let parent = document().create_element("ul").expect("can't create element");
+        let first = child("a");
+        let second = child("b");
+        parent.append_child(&first);
+        parent.append_child(&second);
+
+        let first_node: Node = first.into();
+        let second_node: Node = second.into();
+        reposition(&parent, &Some(first_node.clone()), &second_node);
+
+        let childs: Vec<Node> = parent.child_nodes().iter().collect();
+        assert_eq!(childs, vec![first_node, second_node]);
+    }
+
+    /// A keyed node that moved to the front of the list is relocated
+    /// there instead of staying wherever it used to be.
+    #[test]
+    fn reposition_moves_node_to_the_front() {
+        let parent = document().create_element("ul").expect("can't create element");
+        let a = child("a");
+        let b = child("b");
+        parent.append_child(&a);
+        parent.append_child(&b);
+
+        let a_node: Node = a.into();
+        let b_node: Node = b.into();
+        // `b` is now first in the new order, so it has no previous node.
+        reposition(&parent, &None, &b_node);
+
+        let childs: Vec<Node> = parent.child_nodes().iter().collect();
+        assert_eq!(childs, vec![b_node, a_node]);
+    }
+
+    /// A reused node that belongs at the very end is appended, not left
+    /// wherever it happened to be.
+    #[test]
+    fn reposition_moves_node_to_the_end() {
+        let parent = document().create_element("ul").expect("can't create element");
+        let a = child("a");
+        let b = child("b");
+        parent.append_child(&a);
+        parent.append_child(&b);
+
+        let a_node: Node = a.into();
+        let b_node: Node = b.into();
+        // `a` now follows `b`, which is already the last child, so there's
+        // no sibling to insert `a` before and it's appended instead.
+        reposition(&parent, &Some(b_node.clone()), &a_node);
+
+        let childs: Vec<Node> = parent.child_nodes().iter().collect();
+        assert_eq!(childs, vec![b_node, a_node]);
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt;
 use std::cmp::PartialEq;
 use stdweb::web::{INode, Node, Element};
 use html::{ScopeEnv, Component, Renderable};
-use super::{VDiff, VTag, VText, VComp};
+use super::{VDiff, VTag, VText, VComp, VList};
 
 /// Bind virtual element to a DOM reference.
 pub enum VNode<CTX, COMP: Component<CTX>> {
@@ -16,6 +16,8 @@ pub enum VNode<CTX, COMP: Component<CTX>> {
     VComp(VComp<CTX, COMP>),
     /// A holder for any `Node` (necessary for replacing node).
     VRef(Node),
+    /// A fragment of `VNode`s rendered without a wrapper element.
+    VList(VList<CTX, COMP>),
 }
 
 
@@ -38,6 +40,9 @@ impl<CTX: 'static, COMP: Component<CTX>> VDiff for VNode<CTX, COMP> {
             VNode::VRef(ref node) => {
                 Some(node.to_owned())
             },
+            VNode::VList(ref vlist) => {
+                vlist.get_node()
+            },
         }
     }
 
@@ -50,6 +55,7 @@ impl<CTX: 'static, COMP: Component<CTX>> VDiff for VNode<CTX, COMP> {
             VNode::VRef(node) => {
                 parent.remove_child(&node).expect("can't remove node by VRef")
             },
+            VNode::VList(vlist) => vlist.remove(parent),
         }
     }
 
@@ -66,14 +72,50 @@ impl<CTX: 'static, COMP: Component<CTX>> VDiff for VNode<CTX, COMP> {
             VNode::VComp(ref mut vcomp) => {
                 vcomp.apply(parent, opposite, env);
             }
-            VNode::VRef(_) => {
-                // TODO use it for rendering any tag
-                unimplemented!("node can't be rendered now");
+            VNode::VRef(ref node) => {
+                match opposite {
+                    Some(VNode::VRef(ref opposite_node)) if opposite_node == node => {
+                        // Same node as before, keep it untouched.
+                    }
+                    Some(other) => {
+                        match other.get_node() {
+                            Some(ref sibling) => {
+                                parent.insert_before(node, sibling)
+                                    .expect("can't insert node by VRef");
+                            }
+                            None => {
+                                parent.append_child(node);
+                            }
+                        }
+                        other.remove(parent);
+                    }
+                    None => {
+                        parent.append_child(node);
+                    }
+                }
+            }
+            VNode::VList(ref mut vlist) => {
+                vlist.apply(parent, opposite, env);
             }
         }
     }
 }
 
+impl<CTX, COMP: Component<CTX>> VNode<CTX, COMP> {
+    /// Returns the reconciliation key set on this node, if any. Keyed
+    /// `VTag`/`VComp` nodes are matched up by key (instead of position)
+    /// when a parent diffs its children through `apply`, so reordered
+    /// list items keep their DOM identity and state. `DiffWorker` ignores
+    /// keys and pairs children by position.
+    pub fn key(&self) -> Option<&str> {
+        match *self {
+            VNode::VTag(ref vtag) => vtag.key.as_ref().map(String::as_str),
+            VNode::VComp(ref vcomp) => vcomp.key.as_ref().map(String::as_str),
+            VNode::VText(_) | VNode::VRef(_) | VNode::VList(_) => None,
+        }
+    }
+}
+
 impl<CTX, COMP: Component<CTX>> From<VText<CTX, COMP>> for VNode<CTX, COMP> {
     fn from(vtext: VText<CTX, COMP>) -> Self {
         VNode::VText(vtext)
@@ -92,6 +134,12 @@ impl<CTX, COMP: Component<CTX>> From<VComp<CTX, COMP>> for VNode<CTX, COMP> {
     }
 }
 
+impl<CTX, COMP: Component<CTX>> From<VList<CTX, COMP>> for VNode<CTX, COMP> {
+    fn from(vlist: VList<CTX, COMP>) -> Self {
+        VNode::VList(vlist)
+    }
+}
+
 impl<CTX: 'static, COMP: Component<CTX>, T: ToString> From<T> for VNode<CTX, COMP> {
     fn from(value: T) -> Self {
         VNode::VText(VText::new(value.to_string()))
@@ -111,11 +159,15 @@ impl<CTX, COMP: Component<CTX>> fmt::Debug for VNode<CTX, COMP> {
             &VNode::VText(ref vtext) => vtext.fmt(f),
             &VNode::VComp(_) => "Component<>".fmt(f),
             &VNode::VRef(_) => "NodeReference<>".fmt(f),
+            &VNode::VList(ref vlist) => vlist.fmt(f),
         }
     }
 }
 
-impl<CTX, COMP: Component<CTX>> PartialEq for VNode<CTX, COMP> {
+impl<CTX, COMP: Component<CTX>> PartialEq for VNode<CTX, COMP>
+where
+    COMP::Properties: PartialEq,
+{
     fn eq(&self, other: &VNode<CTX, COMP>) -> bool {
         match *self {
             VNode::VTag(ref vtag_a) => {
@@ -134,14 +186,344 @@ impl<CTX, COMP: Component<CTX>> PartialEq for VNode<CTX, COMP> {
                     _ => false
                 }
             }
-            VNode::VComp(_) => {
-                // TODO Implement it
-                false
+            VNode::VComp(ref vcomp_a) => {
+                match *other {
+                    // Two components are equal if they're the same
+                    // component type constructed with equal props, so
+                    // an unchanged subtree can be skipped during diffing.
+                    VNode::VComp(ref vcomp_b) => {
+                        vcomp_a.type_id == vcomp_b.type_id && vcomp_a.props == vcomp_b.props
+                    },
+                    _ => false
+                }
+            }
+            VNode::VRef(ref node_a) => {
+                match *other {
+                    VNode::VRef(ref node_b) => node_a == node_b,
+                    _ => false
+                }
+            }
+            VNode::VList(ref vlist_a) => {
+                match *other {
+                    VNode::VList(ref vlist_b) => {
+                        vlist_a == vlist_b
+                    },
+                    _ => false
+                }
+            }
+        }
+    }
+}
+
+/// Tags that never have a closing tag or children, per the HTML spec.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input",
+    "link", "meta", "param", "source", "track", "wbr",
+];
+
+impl<CTX, COMP: Component<CTX>> fmt::Display for VNode<CTX, COMP> {
+    /// Renders this node (and its children) as HTML, so a tree can be
+    /// pre-rendered on the server or snapshotted in tests without a live
+    /// DOM or `stdweb`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VNode::VTag(ref vtag) => {
+                write!(f, "<{}", vtag.tag)?;
+                // `attributes` is a `HashMap`, whose iteration order
+                // isn't stable across runs; sort by name so rendering
+                // the same tag twice produces identical output.
+                let mut attributes: Vec<_> = vtag.attributes.iter().collect();
+                attributes.sort_by(|&(a, _), &(b, _)| a.cmp(b));
+                for (name, value) in attributes {
+                    write!(f, " {}=\"{}\"", name, escape_attribute(value))?;
+                }
+                if VOID_ELEMENTS.contains(&vtag.tag.as_str()) {
+                    return write!(f, " />");
+                }
+                write!(f, ">")?;
+                for child in vtag.childs.iter() {
+                    fmt::Display::fmt(child, f)?;
+                }
+                write!(f, "</{}>", vtag.tag)
+            }
+            VNode::VText(ref vtext) => {
+                write!(f, "{}", escape_text(&vtext.text))
+            }
+            VNode::VComp(ref vcomp) => {
+                fmt::Display::fmt(&vcomp.view(), f)
             }
             VNode::VRef(_) => {
-                // TODO Implement it
-                false
+                // A raw DOM node has no HTML source to render server-side.
+                Ok(())
+            }
+            VNode::VList(ref vlist) => {
+                for child in vlist.childs.iter() {
+                    fmt::Display::fmt(child, f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> VNode<CTX, COMP> {
+    /// Renders this node to an HTML string. Handy for server-side
+    /// rendering or snapshot-testing a component's output.
+    pub fn to_html_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ctx;
+
+    struct Comp;
+
+    impl Component<Ctx> for Comp {
+        type Message = ();
+        type Properties = ();
+
+        fn create(_: Self::Properties, _: ScopeEnv<Ctx, Self>) -> Self {
+            Comp
+        }
+
+        fn update(&mut self, _: Self::Message) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn to_html_string_renders_nested_tags_and_escapes_text() {
+        let mut tag: VTag<Ctx, Comp> = VTag::new("div");
+        tag.set_attribute("class", "greeting");
+        tag.add_child(VText::new("<hi>".to_string()).into());
+        let node: VNode<Ctx, Comp> = tag.into();
+
+        assert_eq!(
+            node.to_html_string(),
+            "<div class=\"greeting\">&lt;hi&gt;</div>",
+        );
+    }
+
+    #[test]
+    fn to_html_string_renders_attributes_in_a_stable_sorted_order() {
+        let mut tag: VTag<Ctx, Comp> = VTag::new("input");
+        tag.set_attribute("type", "text");
+        tag.set_attribute("name", "email");
+        tag.set_attribute("id", "field");
+        let node: VNode<Ctx, Comp> = tag.into();
+
+        assert_eq!(
+            node.to_html_string(),
+            "<input id=\"field\" name=\"email\" type=\"text\" />",
+        );
+    }
+
+    #[test]
+    fn to_html_string_renders_void_elements_without_children() {
+        let tag: VTag<Ctx, Comp> = VTag::new("br");
+        let node: VNode<Ctx, Comp> = tag.into();
+
+        assert_eq!(node.to_html_string(), "<br />");
+    }
+
+    // `DiffWorker::step` takes a live `ScopeEnv`, which comes from the
+    // external `html` crate this snapshot doesn't include, so it can't
+    // be driven end to end here (same reason `vlist.rs`'s reorder tests
+    // exercise `reposition` directly rather than `VList::apply`).
+    // `for_stack_push` is the part of `step`'s `VTag`/`VList` arms that
+    // actually produces render order, so it's tested standalone instead.
+    #[test]
+    fn for_stack_push_reverses_so_popping_restores_source_order() {
+        let pushed = for_stack_push(vec!["a", "b", "c"]);
+
+        let mut stack = Vec::new();
+        for item in pushed {
+            stack.push(item);
+        }
+        let mut popped = Vec::new();
+        while let Some(item) = stack.pop() {
+            popped.push(item);
+        }
+
+        assert_eq!(popped, vec!["a", "b", "c"]);
+    }
+}
+
+fn escape_text(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_attribute(input: &str) -> String {
+    escape_text(input).replace('"', "&quot;")
+}
+
+/// A single step of the non-recursive diff/patch algorithm driven by
+/// `DiffWorker`. Diffing a `VTag` or `VList` normally recurses into
+/// `VNode::apply` for every child; instead one instruction per child is
+/// pushed onto an explicit work stack, so the driver loop can pop and
+/// process nodes one at a time instead of recursing arbitrarily deep in
+/// a single synchronous call.
+///
+/// `VText`/`VComp`/`VRef` nodes are still patched through the existing
+/// (recursive) `apply` as a single atomic step, since they don't have
+/// children under this module's control to unroll further.
+enum DiffInstruction<'a, CTX: 'a, COMP: Component<CTX> + 'a> {
+    /// Diff and patch `node` against `opposite`, inside `parent`.
+    Diff {
+        parent: Element,
+        node: &'a mut VNode<CTX, COMP>,
+        opposite: Option<VNode<CTX, COMP>>,
+    },
+    /// Remove `node` (with no replacement) from `parent`.
+    Remove {
+        parent: Element,
+        node: VNode<CTX, COMP>,
+    },
+}
+
+/// Reverses `items` so that, once each is pushed onto a LIFO stack in
+/// the returned order, popping them back off yields `items`' original
+/// (source) order again.
+fn for_stack_push<T>(mut items: Vec<T>) -> Vec<T> {
+    items.reverse();
+    items
+}
+
+/// Drives a non-recursive, resumable diff/patch of a `VNode` tree. Holds
+/// the work stack itself (instead of rebuilding it each call), so a
+/// scheduler can call `step` repeatedly with a budget, yielding back to
+/// the browser in between, and pick up exactly where the last call left
+/// off rather than restarting from the root.
+pub struct DiffWorker<'a, CTX: 'a, COMP: Component<CTX> + 'a> {
+    stack: Vec<DiffInstruction<'a, CTX, COMP>>,
+}
+
+impl<'a, CTX: 'static, COMP: Component<CTX>> DiffWorker<'a, CTX, COMP> {
+    /// Starts a worker that will diff `node` against `opposite` inside
+    /// `parent` once `step` is called.
+    pub fn new(
+        node: &'a mut VNode<CTX, COMP>,
+        parent: &Element,
+        opposite: Option<VNode<CTX, COMP>>,
+    ) -> Self {
+        DiffWorker {
+            stack: vec![DiffInstruction::Diff {
+                parent: parent.clone(),
+                node,
+                opposite,
+            }],
+        }
+    }
+
+    /// Pops and processes up to `max_ops` instructions from the work
+    /// stack instead of recursing into every child, then reports whether
+    /// work remains. The stack persists on `self` between calls, so
+    /// resuming continues exactly where the budget ran out rather than
+    /// restarting the diff.
+    pub fn step(&mut self, env: ScopeEnv<CTX, COMP>, max_ops: usize) -> bool {
+        let mut ops = 0;
+        while ops < max_ops {
+            let instruction = match self.stack.pop() {
+                Some(instruction) => instruction,
+                None => return false,
+            };
+            match instruction {
+                DiffInstruction::Diff { parent, node, opposite } => {
+                    match *node {
+                        VNode::VList(ref mut vlist) => {
+                            let mut opposite_childs = match opposite {
+                                Some(VNode::VList(vlist)) => vlist.childs,
+                                Some(other) => vec![other],
+                                None => Vec::new(),
+                            };
+                            let mut opposite_iter = opposite_childs.drain(..);
+                            // Collect in source order first, since pairing
+                            // each child with its opposite must happen
+                            // forward; push onto the LIFO stack in
+                            // reverse so they still *pop* (and render) in
+                            // source order.
+                            let diffs: Vec<_> = vlist.childs.iter_mut()
+                                .map(|child| DiffInstruction::Diff {
+                                    parent: parent.clone(),
+                                    node: child,
+                                    opposite: opposite_iter.next(),
+                                })
+                                .collect();
+                            for rest in opposite_iter {
+                                self.stack.push(DiffInstruction::Remove {
+                                    parent: parent.clone(),
+                                    node: rest,
+                                });
+                            }
+                            for diff in for_stack_push(diffs) {
+                                self.stack.push(diff);
+                            }
+                        }
+                        VNode::VTag(ref mut vtag) => {
+                            let (element, mut old_childs) = vtag.apply_shallow(&parent, opposite);
+                            let mut old_iter = old_childs.drain(..);
+                            // See the `VList` arm above: collect forward,
+                            // push reversed, so pops come out in source
+                            // order.
+                            let diffs: Vec<_> = vtag.childs.iter_mut()
+                                .map(|child| DiffInstruction::Diff {
+                                    parent: element.clone(),
+                                    node: child,
+                                    opposite: old_iter.next(),
+                                })
+                                .collect();
+                            for rest in old_iter {
+                                self.stack.push(DiffInstruction::Remove {
+                                    parent: element.clone(),
+                                    node: rest,
+                                });
+                            }
+                            for diff in for_stack_push(diffs) {
+                                self.stack.push(diff);
+                            }
+                        }
+                        _ => {
+                            node.apply(&parent, opposite, env.clone());
+                        }
+                    }
+                }
+                DiffInstruction::Remove { parent, node } => {
+                    match node {
+                        VNode::VList(vlist) => {
+                            for child in vlist.childs {
+                                self.stack.push(DiffInstruction::Remove {
+                                    parent: parent.clone(),
+                                    node: child,
+                                });
+                            }
+                        }
+                        VNode::VTag(vtag) => {
+                            let element = vtag.reference.clone();
+                            for child in vtag.childs {
+                                if let Some(ref element) = element {
+                                    self.stack.push(DiffInstruction::Remove {
+                                        parent: element.clone(),
+                                        node: child,
+                                    });
+                                }
+                            }
+                            if let Some(element) = element {
+                                parent.remove_child(&element).expect("can't remove VTag element");
+                            }
+                        }
+                        other => other.remove(&parent),
+                    }
+                }
             }
+            ops += 1;
         }
+        !self.stack.is_empty()
     }
 }
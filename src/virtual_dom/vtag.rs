@@ -0,0 +1,153 @@
+//! This module contains the implementation of an element node `VTag`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use stdweb::web::{document, Element, IElement, INode, Node};
+use html::{Component, ScopeEnv};
+use super::{VDiff, VList, VNode};
+
+/// A type for an element virtual node, bound to an `Element` once
+/// rendered.
+pub struct VTag<CTX, COMP: Component<CTX>> {
+    /// The name of the tag, e.g. `"div"`.
+    pub tag: String,
+    /// Attributes set on the element.
+    pub attributes: HashMap<String, String>,
+    /// Children nodes rendered inside this element.
+    pub childs: Vec<VNode<CTX, COMP>>,
+    /// An optional key used to match this node across renders, so a
+    /// reordered list item keeps its DOM identity (and state, like focus
+    /// or scroll position) instead of being recreated in place.
+    pub key: Option<String>,
+    pub(crate) reference: Option<Element>,
+    _ctx: PhantomData<CTX>,
+    _comp: PhantomData<COMP>,
+}
+
+impl<CTX, COMP: Component<CTX>> VTag<CTX, COMP> {
+    /// Creates a new `VTag` with the given tag name.
+    pub fn new<S: Into<String>>(tag: S) -> Self {
+        VTag {
+            tag: tag.into(),
+            attributes: HashMap::new(),
+            childs: Vec::new(),
+            key: None,
+            reference: None,
+            _ctx: PhantomData,
+            _comp: PhantomData,
+        }
+    }
+
+    /// Sets the reconciliation key for this node.
+    pub fn set_key<S: Into<String>>(&mut self, key: S) {
+        self.key = Some(key.into());
+    }
+
+    /// Sets an attribute on this element.
+    pub fn set_attribute<S: Into<String>>(&mut self, name: S, value: S) {
+        self.attributes.insert(name.into(), value.into());
+    }
+
+    /// Adds a child node.
+    pub fn add_child(&mut self, child: VNode<CTX, COMP>) {
+        self.childs.push(child);
+    }
+}
+
+impl<CTX: 'static, COMP: Component<CTX>> VTag<CTX, COMP> {
+    /// Reconciles this tag's own element and attributes against
+    /// `opposite` (reusing its element when the tag name matches),
+    /// without touching children. Returns the live element and the
+    /// previous children, so callers that want to diff children
+    /// themselves (e.g. `DiffWorker`, to avoid recursing) can do so
+    /// instead of this tag diffing them inline.
+    pub fn apply_shallow(
+        &mut self,
+        parent: &Element,
+        opposite: Option<VNode<CTX, COMP>>,
+    ) -> (Element, Vec<VNode<CTX, COMP>>) {
+        let (reused, old_childs) = match opposite {
+            Some(VNode::VTag(mut old_tag)) if old_tag.tag == self.tag => {
+                if let Some(ref element) = old_tag.reference {
+                    for name in old_tag.attributes.keys() {
+                        if !self.attributes.contains_key(name) {
+                            element.remove_attribute(name);
+                        }
+                    }
+                }
+                (old_tag.reference.take(), mem::replace(&mut old_tag.childs, Vec::new()))
+            }
+            Some(other) => {
+                other.remove(parent);
+                (None, Vec::new())
+            }
+            None => (None, Vec::new()),
+        };
+
+        let element = reused.unwrap_or_else(|| {
+            let element = document()
+                .create_element(&self.tag)
+                .expect("can't create element");
+            parent.append_child(&element);
+            element
+        });
+
+        for (name, value) in self.attributes.iter() {
+            element.set_attribute(name, value).expect("can't set attribute");
+        }
+
+        self.reference = Some(element.clone());
+        (element, old_childs)
+    }
+}
+
+impl<CTX: 'static, COMP: Component<CTX>> VDiff for VTag<CTX, COMP> {
+    type Context = CTX;
+    type Component = COMP;
+
+    /// Get binded node.
+    fn get_node(&self) -> Option<Node> {
+        self.reference.as_ref().map(|element| element.to_owned().into())
+    }
+
+    /// Remove VTag from parent.
+    fn remove(self, parent: &Element) {
+        if let Some(element) = self.reference {
+            parent.remove_child(&element).expect("can't remove VTag element");
+        }
+    }
+
+    /// Reconciles the element and attributes, then diffs children
+    /// recursively against the previous tag's children, matching keyed
+    /// children by key the same way `VList` does.
+    fn apply(
+        &mut self,
+        parent: &Element,
+        opposite: Option<VNode<Self::Context, Self::Component>>,
+        env: ScopeEnv<Self::Context, Self::Component>,
+    ) {
+        let (element, old_childs) = self.apply_shallow(parent, opposite);
+        let mut childs = VList::from(mem::replace(&mut self.childs, Vec::new()));
+        childs.apply(&element, Some(VNode::VList(VList::from(old_childs))), env);
+        self.childs = childs.childs;
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> fmt::Debug for VTag<CTX, COMP> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VTag {{ tag: {:?} }}", self.tag)
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> PartialEq for VTag<CTX, COMP>
+where
+    COMP::Properties: PartialEq,
+{
+    fn eq(&self, other: &VTag<CTX, COMP>) -> bool {
+        self.tag == other.tag
+            && self.attributes == other.attributes
+            && self.childs == other.childs
+    }
+}
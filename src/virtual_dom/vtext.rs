@@ -0,0 +1,83 @@
+//! This module contains the implementation of a text node `VText`.
+
+use std::fmt;
+use std::marker::PhantomData;
+use stdweb::web::{document, Element, INode, Node};
+use html::{Component, ScopeEnv};
+use super::{VDiff, VNode};
+
+/// A type for a virtual text node, bound to a text `Node` once rendered.
+pub struct VText<CTX, COMP: Component<CTX>> {
+    /// Contained text.
+    pub text: String,
+    reference: Option<Node>,
+    _ctx: PhantomData<CTX>,
+    _comp: PhantomData<COMP>,
+}
+
+impl<CTX, COMP: Component<CTX>> VText<CTX, COMP> {
+    /// Creates a new virtual text node with the given content.
+    pub fn new(text: String) -> Self {
+        VText {
+            text,
+            reference: None,
+            _ctx: PhantomData,
+            _comp: PhantomData,
+        }
+    }
+}
+
+impl<CTX: 'static, COMP: Component<CTX>> VDiff for VText<CTX, COMP> {
+    type Context = CTX;
+    type Component = COMP;
+
+    /// Get binded node.
+    fn get_node(&self) -> Option<Node> {
+        self.reference.clone()
+    }
+
+    /// Remove VText from parent.
+    fn remove(self, parent: &Element) {
+        if let Some(node) = self.reference {
+            parent.remove_child(&node).expect("can't remove VText node");
+        }
+    }
+
+    /// Reuses the opposite text node (updating its content in place) if
+    /// one exists, otherwise creates a new one.
+    fn apply(
+        &mut self,
+        parent: &Element,
+        opposite: Option<VNode<Self::Context, Self::Component>>,
+        _env: ScopeEnv<Self::Context, Self::Component>,
+    ) {
+        match opposite {
+            Some(VNode::VText(mut vtext)) => {
+                if let Some(node) = vtext.reference.take() {
+                    if vtext.text != self.text {
+                        node.set_text_content(&self.text);
+                    }
+                    self.reference = Some(node);
+                    return;
+                }
+            }
+            Some(other) => other.remove(parent),
+            None => {}
+        }
+        let node: Node = document().create_text_node(&self.text).into();
+        parent.append_child(&node);
+        self.reference = Some(node);
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> fmt::Debug for VText<CTX, COMP> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VText {{ text: {:?} }}", self.text)
+    }
+}
+
+impl<CTX, COMP: Component<CTX>> PartialEq for VText<CTX, COMP> {
+    fn eq(&self, other: &VText<CTX, COMP>) -> bool {
+        self.text == other.text
+    }
+}